@@ -1,7 +1,12 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 use codec::{Decode, Encode};
-use frame_support::{ensure, traits::Currency, RuntimeDebug};
-use frame_system::{ensure_none, ensure_root, ensure_signed};
+use frame_support::{
+	dispatch::{Dispatchable, GetDispatchInfo},
+	ensure,
+	traits::Currency,
+	RuntimeDebug,
+};
+use frame_system::{ensure_none, ensure_root, ensure_signed, RawOrigin};
 #[cfg(feature = "std")]
 use serde::{self, Deserialize, Deserializer, Serialize, Serializer};
 use sp_io::{crypto::secp256k1_ecdsa_recover, hashing::keccak_256};
@@ -11,6 +16,14 @@ use sp_runtime::transaction_validity::{
 };
 use sp_std::prelude::*;
 
+mod transaction;
+pub use transaction::{Eip1559Transaction, EthTransaction, LegacyTransaction};
+
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
+
 #[derive(Clone, Copy, PartialEq, Eq, Encode, Decode, Default, RuntimeDebug)]
 pub struct EthereumAddress([u8; 20]);
 
@@ -62,13 +75,60 @@ impl sp_std::fmt::Debug for EcdsaSignature {
 	}
 }
 
+/// Half the secp256k1 curve order `n`, used to reject malleable (high-`s`) signatures (EIP-2).
+pub(crate) const SECP256K1_HALF_N: [u8; 32] = [
+	0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+	0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+	0x5D, 0x57, 0x6E, 0x73, 0x57, 0xA4, 0x50, 0x1D,
+	0xDF, 0xE9, 0x2F, 0x46, 0x68, 0x1B, 0x20, 0xA0,
+];
+
+impl EcdsaSignature {
+	/// Normalizes legacy (`v in {27,28}`) and EIP-155 (`v = chainId*2 + 35/36`) recovery ids
+	/// into the `0`/`1` form `secp256k1_ecdsa_recover` expects, validating the embedded chain
+	/// id against `expected_chain_id` and rejecting high-`s` (EIP-2 malleable) signatures.
+	/// Returns `None` if `v` doesn't reduce to `0`/`1` under either scheme, the embedded chain
+	/// id doesn't match, or `s` is malleable.
+	fn normalize(&self, expected_chain_id: u64) -> Option<[u8; 65]> {
+		if &self.0[32..64] > &SECP256K1_HALF_N[..] {
+			return None;
+		}
+		let v = self.0[64];
+		let rec_id = match v {
+			0 | 1 => v,
+			27 | 28 => v - 27,
+			_ if v >= 35 => {
+				let chain_id = (v as u64 - 35) / 2;
+				if chain_id != expected_chain_id {
+					return None;
+				}
+				((v as u64 - 35) % 2) as u8
+			}
+			_ => return None,
+		};
+		let mut sig = self.0;
+		sig[64] = rec_id;
+		Some(sig)
+	}
+}
+
+/// An EIP-712 typed `Message(bytes32 payload,uint256 nonce)` struct, signable by
+/// `eth_signTypedData_v4`-capable wallets (MetaMask, Ledger) with human-readable fields.
 #[derive(Clone, Copy, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
-pub struct Message([u8; 256]);
+pub struct TypedMessage {
+	pub payload: [u8; 32],
+	pub nonce: u64,
+}
 
 /// The balance type of this module.
 pub type BalanceOf<T> =
 <<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
 
+/// Weight charged per byte of `verify_transaction`'s raw transaction envelope, so its RLP-decode
+/// plus `keccak_256`/`secp256k1_ecdsa_recover` cost scales with the size of the attacker-supplied
+/// input instead of being charged a flat fee regardless of length.
+const TRANSACTION_BYTE_WEIGHT: u64 = 100;
+
 
 pub use pallet::*;
 
@@ -82,22 +142,67 @@ pub mod pallet {
 	pub trait Config: frame_system::Config {
 		/// Because this pallet emits events, it depends on the runtime's definition of an event.
 		type Event: From<Event<Self>> + IsType<<Self as frame_system::Config>::Event>;
-		type Call: From<Call<Self>>;
+		type Call: Parameter
+			+ From<Call<Self>>
+			+ Dispatchable<Origin = Self::Origin>
+			+ GetDispatchInfo;
 		type Currency: Currency<Self::AccountId>;
+		/// The chain id used when recovering EIP-712 typed-data signatures, and checked against
+		/// the chain id embedded in EIP-155 `v` values on every signature recovery.
+		type ChainId: Get<u64>;
+		/// The `name` field of this pallet's EIP-712 domain.
+		type Eip712Name: Get<&'static [u8]>;
+		/// The `version` field of this pallet's EIP-712 domain.
+		type Eip712Version: Get<&'static [u8]>;
+		/// The `verifyingContract` field of this pallet's EIP-712 domain.
+		type VerifyingContract: Get<EthereumAddress>;
+		/// The maximum length, in bytes, of a `Message` payload accepted by `verify_message`.
+		#[pallet::constant]
+		type MaxMessageLength: Get<u32>;
+		/// The maximum length, in bytes, of the raw Ethereum transaction envelope accepted by
+		/// `verify_transaction`.
+		#[pallet::constant]
+		type MaxTransactionLength: Get<u32>;
 	}
 	#[pallet::pallet]
 	#[pallet::generate_store(pub(super) trait Store)]
 	pub struct Pallet<T>(_);
 
+	/// A variable-length message payload, bounded by `T::MaxMessageLength`.
+	#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+	pub struct Message<T: Config>(pub BoundedVec<u8, T::MaxMessageLength>);
+
+	/// Records that a message (keyed by the keccak256 hash of its payload, or by its EIP-712
+	/// `hashStruct` for a typed message) has already been verified, and by whom, so the same
+	/// signature can't be replayed.
+	#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+	pub struct MessageRecord<T: Config> {
+		pub signer: EthereumAddress,
+		pub block: T::BlockNumber,
+	}
+
 	#[pallet::storage]
 	#[pallet::getter(fn message_state)]
-	pub type MessageState<T: Config> = StorageMap<_, Blake2_128Concat, Message, bool>;
+	pub type MessageState<T: Config> = StorageMap<_, Blake2_128Concat, [u8; 32], MessageRecord<T>>;
+
+	/// Ethereum addresses bound to the Substrate account allowed to submit on their behalf.
+	#[pallet::storage]
+	#[pallet::getter(fn address_binding)]
+	pub type AddressBinding<T: Config> = StorageMap<_, Blake2_128Concat, EthereumAddress, T::AccountId>;
+
+	/// The next nonce expected from `account` when dispatching a meta-transaction via `call`.
+	#[pallet::storage]
+	#[pallet::getter(fn nonce)]
+	pub type Nonces<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, u64, ValueQuery>;
 
 	#[pallet::event]
 	#[pallet::metadata(T::AccountId = "AccountId", BalanceOf<T> = "Balance")]
 	#[pallet::generate_deposit(pub(super) fn deposit_event)]
 	pub enum Event<T: Config>{
-		MessageVerified(T::AccountId, Message, bool),
+		MessageVerified(T::AccountId, Message<T>, EthereumAddress),
+		TypedMessageVerified(T::AccountId, TypedMessage, EthereumAddress),
+		CallDispatched(T::AccountId, DispatchResult),
+		TransactionVerified(T::AccountId, EthereumAddress),
 	}
 
 	#[pallet::error]
@@ -108,6 +213,10 @@ pub mod pallet {
 		InvalidSigner,
 		/// The message has been verified
 		MessageAlreadyVerified,
+		/// The supplied nonce does not match the account's expected nonce.
+		InvalidNonce,
+		/// The raw bytes could not be RLP-decoded as a legacy or EIP-1559 transaction envelope.
+		InvalidTransactionEnvelope,
 	}
 
 	#[pallet::call]
@@ -116,17 +225,119 @@ pub mod pallet {
 		pub fn verify_message(
 			origin: OriginFor<T>,
 			account: T::AccountId,
-			message: Message,
+			message: Message<T>,
 			signature: EcdsaSignature
 		) -> DispatchResultWithPostInfo {
 			let _ = ensure_none(origin)?;
-			ensure!(MessageState::<T>::get(&message).is_some(), Error::<T>::MessageAlreadyVerified);
+			let message_hash = keccak_256(&message.0);
+			ensure!(MessageState::<T>::get(&message_hash).is_none(), Error::<T>::MessageAlreadyVerified);
 			let address = Encode::encode(&account);
 			let signer = Self::eth_recover(&signature, &address, &message.0)
 				.ok_or(Error::<T>::InvalidSignature)?;
-			// TODO: Verify message signature
-			// ensure!(signer == message signer, Error::<T>::InvalidSigner);
-			Self::deposit_event(Event::MessageVerified(account, message, true));
+			let bound_account = AddressBinding::<T>::get(&signer).ok_or(Error::<T>::InvalidSigner)?;
+			ensure!(bound_account == account, Error::<T>::InvalidSigner);
+			MessageState::<T>::insert(
+				&message_hash,
+				MessageRecord { signer, block: frame_system::Pallet::<T>::block_number() },
+			);
+			Self::deposit_event(Event::MessageVerified(account, message, signer));
+			Ok(().into())
+		}
+
+		/// Like `verify_message`, but recovers the signer over an EIP-712 typed-data digest
+		/// instead of the legacy `personal_sign` prefix, so `eth_signTypedData_v4` wallets can
+		/// sign a structured `Message(bytes32 payload,uint256 nonce)` payload.
+		#[pallet::weight(0 + T::DbWeight::get().reads_writes(1,1))]
+		pub fn verify_typed_message(
+			origin: OriginFor<T>,
+			account: T::AccountId,
+			message: TypedMessage,
+			signature: EcdsaSignature
+		) -> DispatchResultWithPostInfo {
+			let _ = ensure_none(origin)?;
+			let message_hash = Self::hash_typed_message(&message);
+			ensure!(MessageState::<T>::get(&message_hash).is_none(), Error::<T>::MessageAlreadyVerified);
+			let signer = Self::eth_recover_typed(&signature, &message)
+				.ok_or(Error::<T>::InvalidSignature)?;
+			let bound_account = AddressBinding::<T>::get(&signer).ok_or(Error::<T>::InvalidSigner)?;
+			ensure!(bound_account == account, Error::<T>::InvalidSigner);
+			MessageState::<T>::insert(
+				&message_hash,
+				MessageRecord { signer, block: frame_system::Pallet::<T>::block_number() },
+			);
+			Self::deposit_event(Event::TypedMessageVerified(account, message, signer));
+			Ok(().into())
+		}
+
+		/// Binds an Ethereum address to the Substrate account allowed to act on its behalf.
+		///
+		/// Root-only: this is the trust anchor for the claims subsystem, mirroring Polkadot's
+		/// `claims` pallet where a binding is established out-of-band before any signature is
+		/// accepted.
+		#[pallet::weight(0 + T::DbWeight::get().reads_writes(0,1))]
+		pub fn register_address(
+			origin: OriginFor<T>,
+			ethereum_address: EthereumAddress,
+			account: T::AccountId,
+		) -> DispatchResultWithPostInfo {
+			ensure_root(origin)?;
+			AddressBinding::<T>::insert(&ethereum_address, &account);
+			Ok(().into())
+		}
+
+		/// Dispatches `call` as `account`, authorized by an Ethereum signature over
+		/// `(call, nonce, account)` instead of a Substrate signature. This lets Ethereum-key
+		/// holders submit arbitrary signed runtime calls without ever holding a Substrate key,
+		/// paid for by a relayer through the unsigned path.
+		#[pallet::weight({
+			let dispatch_info = call.get_dispatch_info();
+			(
+				dispatch_info.weight.saturating_add(T::DbWeight::get().reads_writes(2, 2)),
+				dispatch_info.class,
+			)
+		})]
+		pub fn call(
+			origin: OriginFor<T>,
+			account: T::AccountId,
+			call: Box<<T as Config>::Call>,
+			nonce: u64,
+			signature: EcdsaSignature,
+		) -> DispatchResultWithPostInfo {
+			let _ = ensure_none(origin)?;
+			ensure!(nonce == Nonces::<T>::get(&account), Error::<T>::InvalidNonce);
+			let payload = (&call, nonce, &account).encode();
+			let signer = Self::eth_recover(&signature, &payload, &[])
+				.ok_or(Error::<T>::InvalidSignature)?;
+			let bound_account = AddressBinding::<T>::get(&signer).ok_or(Error::<T>::InvalidSigner)?;
+			ensure!(bound_account == account, Error::<T>::InvalidSigner);
+			Nonces::<T>::insert(&account, nonce + 1);
+			let result = call.dispatch(RawOrigin::Signed(account.clone()).into());
+			Self::deposit_event(Event::CallDispatched(
+				account,
+				result.map(|_| ()).map_err(|e| e.error),
+			));
+			Ok(().into())
+		}
+
+		/// Attests that `account` authored a fully-formed signed Ethereum transaction, by
+		/// RLP-decoding `raw_transaction` (legacy, EIP-155, or EIP-1559) and recovering its
+		/// signer, enabling bridge/exchange-proof use cases.
+		#[pallet::weight(
+			T::DbWeight::get().reads_writes(1, 1)
+				.saturating_add((raw_transaction.len() as u64).saturating_mul(TRANSACTION_BYTE_WEIGHT))
+		)]
+		pub fn verify_transaction(
+			origin: OriginFor<T>,
+			account: T::AccountId,
+			raw_transaction: BoundedVec<u8, T::MaxTransactionLength>,
+		) -> DispatchResultWithPostInfo {
+			let _ = ensure_none(origin)?;
+			let transaction =
+				EthTransaction::decode(&raw_transaction).ok_or(Error::<T>::InvalidTransactionEnvelope)?;
+			let signer = transaction.recover_signer().ok_or(Error::<T>::InvalidSignature)?;
+			let bound_account = AddressBinding::<T>::get(&signer).ok_or(Error::<T>::InvalidSigner)?;
+			ensure!(bound_account == account, Error::<T>::InvalidSigner);
+			Self::deposit_event(Event::TransactionVerified(account, signer));
 			Ok(().into())
 		}
 	}
@@ -153,10 +364,65 @@ pub mod pallet {
 		// Attempts to recover the Ethereum address from a message signature signed by using
 		// the Ethereum RPC's `personal_sign` and `eth_sign`.
 		fn eth_recover(s: &EcdsaSignature, what: &[u8], extra: &[u8]) -> Option<EthereumAddress> {
+			let normalized = s.normalize(T::ChainId::get())?;
 			let msg = keccak_256(&Self::ethereum_signable_message(what, extra));
 			let mut res = EthereumAddress::default();
-			res.0
-				.copy_from_slice(&keccak_256(&secp256k1_ecdsa_recover(&s.0, &msg).ok()?[..])[12..]);
+			res.0.copy_from_slice(
+				&keccak_256(&secp256k1_ecdsa_recover(&normalized, &msg).ok()?[..])[12..],
+			);
+			Some(res)
+		}
+
+		// Left-pads a `uint256` into its 32-byte big-endian ABI encoding.
+		fn pad_u256(n: u64) -> [u8; 32] {
+			let mut buf = [0u8; 32];
+			buf[24..].copy_from_slice(&n.to_be_bytes());
+			buf
+		}
+
+		// Left-pads an `address` into its 32-byte ABI encoding.
+		fn pad_address(addr: &EthereumAddress) -> [u8; 32] {
+			let mut buf = [0u8; 32];
+			buf[12..].copy_from_slice(&addr.0);
+			buf
+		}
+
+		// `keccak256(typeHash(EIP712Domain) || keccak256(name) || keccak256(version) || chainId || verifyingContract)`.
+		fn eip712_domain_separator() -> [u8; 32] {
+			const EIP712_DOMAIN_TYPEHASH: &[u8] =
+				b"EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)";
+			let mut buf = Vec::with_capacity(32 * 4);
+			buf.extend_from_slice(&keccak_256(EIP712_DOMAIN_TYPEHASH));
+			buf.extend_from_slice(&keccak_256(T::Eip712Name::get()));
+			buf.extend_from_slice(&keccak_256(T::Eip712Version::get()));
+			buf.extend_from_slice(&Self::pad_u256(T::ChainId::get()));
+			buf.extend_from_slice(&Self::pad_address(&T::VerifyingContract::get()));
+			keccak_256(&buf)
+		}
+
+		// `hashStruct(message) = keccak256(typeHash(Message) || encodeData(message))`.
+		fn hash_typed_message(message: &TypedMessage) -> [u8; 32] {
+			const MESSAGE_TYPEHASH: &[u8] = b"Message(bytes32 payload,uint256 nonce)";
+			let mut buf = Vec::with_capacity(32 * 3);
+			buf.extend_from_slice(&keccak_256(MESSAGE_TYPEHASH));
+			buf.extend_from_slice(&message.payload);
+			buf.extend_from_slice(&Self::pad_u256(message.nonce));
+			keccak_256(&buf)
+		}
+
+		// Attempts to recover the Ethereum address from an EIP-712 typed-data signature over a
+		// `Message` struct, as produced by `eth_signTypedData_v4` (MetaMask, Ledger).
+		fn eth_recover_typed(s: &EcdsaSignature, message: &TypedMessage) -> Option<EthereumAddress> {
+			let normalized = s.normalize(T::ChainId::get())?;
+			let mut digest_input = Vec::with_capacity(2 + 32 + 32);
+			digest_input.extend_from_slice(&[0x19, 0x01]);
+			digest_input.extend_from_slice(&Self::eip712_domain_separator());
+			digest_input.extend_from_slice(&Self::hash_typed_message(message));
+			let digest = keccak_256(&digest_input);
+			let mut res = EthereumAddress::default();
+			res.0.copy_from_slice(
+				&keccak_256(&secp256k1_ecdsa_recover(&normalized, &digest).ok()?[..])[12..],
+			);
 			Some(res)
 		}
 	}
@@ -167,23 +433,52 @@ pub mod pallet {
 		fn validate_unsigned(_source: TransactionSource, call: &Self::Call) -> TransactionValidity {
 			const PRIORITY: u64 = 100;
 
-			let (maybe_signer, tx_hash) = match call {
+			let (maybe_signer, requires, provides) = match call {
 				Call::verify_message(account, message, eth_signature) => {
+					let message_hash = keccak_256(&message.0);
+					ensure!(MessageState::<T>::get(&message_hash).is_none(), InvalidTransaction::Stale);
 					let address = Encode::encode(&account);
-					(
-						Self::eth_recover(&eth_signature, &address, &message.0),
-						message,
-					)
+					let signer = Self::eth_recover(&eth_signature, &address, &message.0);
+					(signer, vec![], vec![("claims-message", message_hash).encode()])
+				}
+				Call::verify_typed_message(_account, message, eth_signature) => {
+					let message_hash = Self::hash_typed_message(message);
+					ensure!(MessageState::<T>::get(&message_hash).is_none(), InvalidTransaction::Stale);
+					let signer = Self::eth_recover_typed(&eth_signature, &message);
+					(signer, vec![], vec![("claims-typed-message", message_hash).encode()])
+				}
+				Call::call(account, inner_call, nonce, eth_signature) => {
+					let expected_nonce = Nonces::<T>::get(account);
+					ensure!(*nonce >= expected_nonce, InvalidTransaction::Stale);
+					let payload = (inner_call, nonce, account).encode();
+					let signer = Self::eth_recover(&eth_signature, &payload, &[]);
+					// Nonce-ordered like any other account-nonce extrinsic, so the pool can tell
+					// that a `call` for nonce `n` must come after the one for nonce `n - 1`.
+					let requires = if *nonce > expected_nonce {
+						vec![("claims-call", account, *nonce - 1).encode()]
+					} else {
+						vec![]
+					};
+					let provides = vec![("claims-call", account, *nonce).encode()];
+					(signer, requires, provides)
+				}
+				Call::verify_transaction(account, raw_transaction) => {
+					let signer = EthTransaction::decode(raw_transaction).and_then(|tx| tx.recover_signer());
+					(signer, vec![], vec![("claims-transaction", account, raw_transaction).encode()])
 				}
 				_ => return Err(InvalidTransaction::Call.into()),
 			};
 
 			let signer = maybe_signer.ok_or(InvalidTransaction::BadProof)?;
+			ensure!(
+				AddressBinding::<T>::get(&signer).is_some(),
+				InvalidTransaction::Custom(ValidityError::InvalidSigner.into())
+			);
 
 			Ok(ValidTransaction {
 				priority: PRIORITY,
-				requires: vec![],
-				provides: vec![("claims", signer).encode()],
+				requires,
+				provides,
 				longevity: TransactionLongevity::max_value(),
 				propagate: true,
 			})
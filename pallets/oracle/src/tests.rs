@@ -0,0 +1,169 @@
+use core::convert::TryFrom;
+
+use codec::Encode;
+use frame_support::{assert_noop, assert_ok, BoundedVec};
+use frame_system::RawOrigin;
+use sp_core::{ecdsa, Pair};
+use sp_io::{crypto::secp256k1_ecdsa_recover, hashing::keccak_256};
+use sp_runtime::DispatchError;
+
+use crate::{mock::*, EcdsaSignature, Error, EthereumAddress, Message, TypedMessage};
+
+fn from_hex(s: &str) -> Vec<u8> {
+	(0..s.len())
+		.step_by(2)
+		.map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+		.collect()
+}
+
+fn address(hex: &str) -> EthereumAddress {
+	let mut address = EthereumAddress::default();
+	address.0.copy_from_slice(&from_hex(hex));
+	address
+}
+
+// Mirrors the pallet's private `ethereum_signable_message`: it isn't exported, so the
+// `personal_sign`-style prefix has to be reconstructed here to sign over the exact digest
+// `eth_recover` will recompute.
+fn ethereum_signable_message(what: &[u8], extra: &[u8]) -> Vec<u8> {
+	let mut l = what.len() + extra.len();
+	let mut rev = Vec::new();
+	while l > 0 {
+		rev.push(b'0' + (l % 10) as u8);
+		l /= 10;
+	}
+	let mut v = b"\x19Ethereum Signed Message:\n".to_vec();
+	v.extend(rev.into_iter().rev());
+	v.extend_from_slice(what);
+	v.extend_from_slice(extra);
+	v
+}
+
+// Signs `(what, extra)` the same way `eth_recover` expects, with a freshly generated secp256k1
+// key, and returns the signature alongside the Ethereum address it recovers to (derived the same
+// way `eth_recover` derives one: keccak256 of the recovered uncompressed public key).
+fn sign_eth(pair: &ecdsa::Pair, what: &[u8], extra: &[u8]) -> (EcdsaSignature, EthereumAddress) {
+	let digest = keccak_256(&ethereum_signable_message(what, extra));
+	let sig = pair.sign_prehashed(&digest);
+	let recovered_pubkey = secp256k1_ecdsa_recover(&sig.0, &digest).expect("valid signature");
+	let mut signer = EthereumAddress::default();
+	signer.0.copy_from_slice(&keccak_256(&recovered_pubkey)[12..]);
+	(EcdsaSignature(sig.0), signer)
+}
+
+// A real secp256k1 key signed an EIP-712 `Message(bytes32 payload,uint256 nonce)` struct over the
+// domain (name `CableOracle`, version `1`, chain id `1`, verifying contract 0x1111...1111) that
+// `Test`'s `mock.rs` configures the pallet with, as a MetaMask `eth_signTypedData_v4` call would;
+// the vector was produced by an independent, from-scratch keccak256/secp256k1 implementation and
+// its signer checked against a second, separate recovery before being hardcoded here.
+fn typed_message_fixture() -> (TypedMessage, EcdsaSignature, EthereumAddress) {
+	let mut payload = [0u8; 32];
+	payload.copy_from_slice(&from_hex("d7f0774a95e98bb03e00fde3483405a112779a9dbad3f2af9c0f5710982160\
+		11"));
+	let message = TypedMessage { payload, nonce: 7 };
+
+	let mut sig = [0u8; 65];
+	sig[..32].copy_from_slice(&from_hex("48720fe30f4d7b7152656bac0d53349b88cb7dadc36e0786eb0bb5975d357\
+		725"));
+	sig[32..64].copy_from_slice(&from_hex("1ed9f4844d6dbe272108dd261d56f5e067cbaf1f92d5504615f92c87c08b\
+		478f"));
+	sig[64] = 28;
+	let signature = EcdsaSignature(sig);
+
+	(message, signature, address("e35ddecb3ae0964de6f18d145675baa371c51b2b"))
+}
+
+#[test]
+fn verify_typed_message_recovers_signer_and_rejects_replay() {
+	new_test_ext().execute_with(|| {
+		let (message, signature, signer) = typed_message_fixture();
+		Oracle::register_address(RawOrigin::Root.into(), signer, 1).unwrap();
+
+		assert_ok!(Oracle::verify_typed_message(
+			RawOrigin::None.into(),
+			1,
+			message.clone(),
+			signature.clone()
+		));
+
+		// The same signed payload must not verify twice: that's the replay protection this
+		// pallet relies on to stop a captured `eth_signTypedData_v4` signature being resubmitted.
+		assert_noop!(
+			Oracle::verify_typed_message(RawOrigin::None.into(), 1, message, signature),
+			Error::<Test>::MessageAlreadyVerified
+		);
+	});
+}
+
+#[test]
+fn verify_message_rejects_an_unbound_signer() {
+	new_test_ext().execute_with(|| {
+		let pair = ecdsa::Pair::from_seed(&[3u8; 32]);
+		let message: Message<Test> = Message(BoundedVec::try_from(b"hello".to_vec()).unwrap());
+		let account: u64 = 1;
+		let (signature, signer) = sign_eth(&pair, &Encode::encode(&account), &message.0);
+
+		// The signature recovers fine, but `signer` was never bound to any account via
+		// `register_address`, so the extrinsic must still refuse it.
+		assert_noop!(
+			Oracle::verify_message(RawOrigin::None.into(), account, message, signature),
+			Error::<Test>::InvalidSigner
+		);
+		assert!(Oracle::address_binding(signer).is_none());
+	});
+}
+
+#[test]
+fn register_address_requires_root_origin() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			Oracle::register_address(RawOrigin::Signed(1).into(), EthereumAddress::default(), 1),
+			DispatchError::BadOrigin
+		);
+	});
+}
+
+#[test]
+fn call_rejects_a_mismatched_nonce() {
+	new_test_ext().execute_with(|| {
+		let inner_call: Call = frame_system::Call::<Test>::remark(vec![]).into();
+		assert_noop!(
+			Oracle::call(
+				RawOrigin::None.into(),
+				1,
+				Box::new(inner_call),
+				Oracle::nonce(1) + 1,
+				EcdsaSignature([0u8; 65]),
+			),
+			Error::<Test>::InvalidNonce
+		);
+	});
+}
+
+#[test]
+fn call_dispatches_the_inner_call_and_bumps_the_nonce() {
+	new_test_ext().execute_with(|| {
+		// `frame_system` drops events deposited at block 0 (genesis).
+		System::set_block_number(1);
+		let pair = ecdsa::Pair::from_seed(&[5u8; 32]);
+		let account: u64 = 1;
+		let nonce = Oracle::nonce(account);
+		let inner_call: Call = frame_system::Call::<Test>::remark(vec![1, 2, 3]).into();
+		let boxed_call = Box::new(inner_call);
+		let payload = (&boxed_call, nonce, &account).encode();
+		let (signature, signer) = sign_eth(&pair, &payload, &[]);
+		Oracle::register_address(RawOrigin::Root.into(), signer, account).unwrap();
+
+		assert_ok!(Oracle::call(RawOrigin::None.into(), account, boxed_call, nonce, signature));
+		assert_eq!(Oracle::nonce(account), nonce + 1);
+
+		let dispatched = System::events()
+			.into_iter()
+			.find_map(|record| match record.event {
+				Event::Oracle(crate::Event::CallDispatched(acc, result)) => Some((acc, result)),
+				_ => None,
+			})
+			.expect("CallDispatched event was deposited");
+		assert_eq!(dispatched, (account, Ok(())));
+	});
+}
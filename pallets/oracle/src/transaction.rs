@@ -0,0 +1,400 @@
+//! RLP-decoding of fully-formed signed Ethereum transaction envelopes (legacy, EIP-155, and
+//! EIP-1559), so the chain can attest that a given Ethereum account authored a specific
+//! on-chain Ethereum transaction. Mirrors Forest's `eth/transaction.rs` handling of these
+//! envelopes, enabling bridge/exchange-proof use cases.
+
+use codec::{Decode, Encode};
+use frame_support::RuntimeDebug;
+use rlp::{Rlp, RlpStream};
+use sp_io::{crypto::secp256k1_ecdsa_recover, hashing::keccak_256};
+use sp_std::prelude::*;
+
+use crate::{EcdsaSignature, EthereumAddress, SECP256K1_HALF_N};
+
+/// A legacy (pre-EIP-155) or EIP-155 replay-protected transaction.
+#[derive(Clone, PartialEq, Encode, Decode, RuntimeDebug)]
+pub struct LegacyTransaction {
+	pub nonce: u64,
+	pub gas_price: u128,
+	pub gas_limit: u64,
+	pub to: Option<EthereumAddress>,
+	pub value: u128,
+	pub data: Vec<u8>,
+	/// `0` for a pre-EIP-155 transaction that carries no replay protection.
+	pub chain_id: u64,
+	pub signature: EcdsaSignature,
+}
+
+impl LegacyTransaction {
+	// `keccak256(rlp([nonce, gasPrice, gasLimit, to, value, data]))` for a genuine pre-EIP-155
+	// transaction (`chain_id == 0`, carrying no replay protection), or
+	// `keccak256(rlp([nonce, gasPrice, gasLimit, to, value, data, chainId, 0, 0]))` once real
+	// EIP-155 replay protection (`v >= 35`) was used.
+	fn signing_hash(&self) -> [u8; 32] {
+		let mut stream = RlpStream::new_list(if self.chain_id == 0 { 6 } else { 9 });
+		append_uint(&mut stream, self.nonce as u128);
+		append_uint(&mut stream, self.gas_price);
+		append_uint(&mut stream, self.gas_limit as u128);
+		append_address(&mut stream, &self.to);
+		append_uint(&mut stream, self.value);
+		stream.append(&self.data);
+		if self.chain_id != 0 {
+			append_uint(&mut stream, self.chain_id as u128);
+			stream.append_empty_data();
+			stream.append_empty_data();
+		}
+		keccak_256(&stream.out())
+	}
+}
+
+/// An EIP-1559 (type `0x02`) dynamic-fee transaction.
+#[derive(Clone, PartialEq, Encode, Decode, RuntimeDebug)]
+pub struct Eip1559Transaction {
+	pub chain_id: u64,
+	pub nonce: u64,
+	pub max_priority_fee_per_gas: u128,
+	pub max_fee_per_gas: u128,
+	pub gas_limit: u64,
+	pub to: Option<EthereumAddress>,
+	pub value: u128,
+	pub data: Vec<u8>,
+	pub access_list: Vec<(EthereumAddress, Vec<[u8; 32]>)>,
+	pub signature: EcdsaSignature,
+}
+
+impl Eip1559Transaction {
+	// `keccak256(0x02 || rlp([chainId, nonce, maxPriorityFee, maxFee, gasLimit, to, value, data, accessList]))`.
+	fn signing_hash(&self) -> [u8; 32] {
+		let mut stream = RlpStream::new_list(9);
+		append_uint(&mut stream, self.chain_id as u128);
+		append_uint(&mut stream, self.nonce as u128);
+		append_uint(&mut stream, self.max_priority_fee_per_gas);
+		append_uint(&mut stream, self.max_fee_per_gas);
+		append_uint(&mut stream, self.gas_limit as u128);
+		append_address(&mut stream, &self.to);
+		append_uint(&mut stream, self.value);
+		stream.append(&self.data);
+		stream.begin_list(self.access_list.len());
+		for (address, storage_keys) in &self.access_list {
+			stream.begin_list(2);
+			stream.append(&&address.0[..]);
+			stream.begin_list(storage_keys.len());
+			for key in storage_keys {
+				stream.append(&&key[..]);
+			}
+		}
+
+		let encoded = stream.out();
+		let mut out = Vec::with_capacity(1 + encoded.len());
+		out.push(0x02u8);
+		out.extend_from_slice(&encoded);
+		keccak_256(&out)
+	}
+}
+
+/// A fully-formed signed Ethereum transaction envelope.
+#[derive(Clone, PartialEq, Encode, Decode, RuntimeDebug)]
+pub enum EthTransaction {
+	Legacy(LegacyTransaction),
+	Eip1559(Eip1559Transaction),
+}
+
+impl EthTransaction {
+	/// RLP-decodes a signed transaction envelope: an EIP-1559 transaction if `raw` starts with
+	/// the `0x02` transaction-type byte, otherwise a legacy/EIP-155 transaction.
+	pub fn decode(raw: &[u8]) -> Option<Self> {
+		match raw.split_first()? {
+			(0x02, rest) => decode_eip1559(rest).map(EthTransaction::Eip1559),
+			_ => decode_legacy(raw).map(EthTransaction::Legacy),
+		}
+	}
+
+	fn signing_hash(&self) -> [u8; 32] {
+		match self {
+			EthTransaction::Legacy(tx) => tx.signing_hash(),
+			EthTransaction::Eip1559(tx) => tx.signing_hash(),
+		}
+	}
+
+	fn signature(&self) -> &EcdsaSignature {
+		match self {
+			EthTransaction::Legacy(tx) => &tx.signature,
+			EthTransaction::Eip1559(tx) => &tx.signature,
+		}
+	}
+
+	/// Recovers the Ethereum address that signed this transaction.
+	pub fn recover_signer(&self) -> Option<EthereumAddress> {
+		let digest = self.signing_hash();
+		let mut res = EthereumAddress::default();
+		res.0.copy_from_slice(
+			&keccak_256(&secp256k1_ecdsa_recover(&self.signature().0, &digest).ok()?[..])[12..],
+		);
+		Some(res)
+	}
+}
+
+// Appends a `uint` in its canonical (no leading zeros, empty for zero) RLP encoding.
+fn append_uint(stream: &mut RlpStream, value: u128) {
+	if value == 0 {
+		stream.append_empty_data();
+		return;
+	}
+	let bytes = value.to_be_bytes();
+	let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len() - 1);
+	stream.append(&&bytes[start..]);
+}
+
+// Appends `to` as a 20-byte address, or an empty string for contract creation.
+fn append_address(stream: &mut RlpStream, to: &Option<EthereumAddress>) {
+	match to {
+		Some(address) => {
+			stream.append(&&address.0[..]);
+		}
+		None => {
+			stream.append_empty_data();
+		}
+	}
+}
+
+fn decode_uint64(rlp: &Rlp, index: usize) -> Option<u64> {
+	let bytes = rlp.at(index).ok()?.data().ok()?;
+	if bytes.len() > 8 {
+		return None;
+	}
+	let mut buf = [0u8; 8];
+	buf[8 - bytes.len()..].copy_from_slice(bytes);
+	Some(u64::from_be_bytes(buf))
+}
+
+fn decode_uint128(rlp: &Rlp, index: usize) -> Option<u128> {
+	let bytes = rlp.at(index).ok()?.data().ok()?;
+	if bytes.len() > 16 {
+		return None;
+	}
+	let mut buf = [0u8; 16];
+	buf[16 - bytes.len()..].copy_from_slice(bytes);
+	Some(u128::from_be_bytes(buf))
+}
+
+fn decode_u256_be(rlp: &Rlp, index: usize) -> Option<[u8; 32]> {
+	let bytes = rlp.at(index).ok()?.data().ok()?;
+	if bytes.len() > 32 {
+		return None;
+	}
+	let mut buf = [0u8; 32];
+	buf[32 - bytes.len()..].copy_from_slice(bytes);
+	Some(buf)
+}
+
+fn decode_to(rlp: &Rlp, index: usize) -> Option<Option<EthereumAddress>> {
+	let bytes = rlp.at(index).ok()?.data().ok()?;
+	if bytes.is_empty() {
+		return Some(None);
+	}
+	if bytes.len() != 20 {
+		return None;
+	}
+	let mut address = EthereumAddress::default();
+	address.0.copy_from_slice(bytes);
+	Some(Some(address))
+}
+
+// Normalizes a legacy `v` value into a `0`/`1` recovery id and, for EIP-155, the embedded chain id.
+fn normalize_legacy_v(v: u64) -> Option<(u8, u64)> {
+	match v {
+		0 | 1 => Some((v as u8, 0)),
+		27 | 28 => Some(((v - 27) as u8, 0)),
+		_ if v >= 35 => Some((((v - 35) % 2) as u8, (v - 35) / 2)),
+		_ => None,
+	}
+}
+
+fn decode_legacy(raw: &[u8]) -> Option<LegacyTransaction> {
+	let rlp = Rlp::new(raw);
+	if !rlp.is_list() || rlp.item_count().ok()? != 9 {
+		return None;
+	}
+
+	let nonce = decode_uint64(&rlp, 0)?;
+	let gas_price = decode_uint128(&rlp, 1)?;
+	let gas_limit = decode_uint64(&rlp, 2)?;
+	let to = decode_to(&rlp, 3)?;
+	let value = decode_uint128(&rlp, 4)?;
+	let data = rlp.at(5).ok()?.data().ok()?.to_vec();
+	let v = decode_uint64(&rlp, 6)?;
+	let r = decode_u256_be(&rlp, 7)?;
+	let s = decode_u256_be(&rlp, 8)?;
+	if s > SECP256K1_HALF_N {
+		return None;
+	}
+	let (rec_id, chain_id) = normalize_legacy_v(v)?;
+
+	let mut sig = [0u8; 65];
+	sig[..32].copy_from_slice(&r);
+	sig[32..64].copy_from_slice(&s);
+	sig[64] = rec_id;
+
+	Some(LegacyTransaction {
+		nonce,
+		gas_price,
+		gas_limit,
+		to,
+		value,
+		data,
+		chain_id,
+		signature: EcdsaSignature(sig),
+	})
+}
+
+fn decode_access_list(rlp: &Rlp) -> Option<Vec<(EthereumAddress, Vec<[u8; 32]>)>> {
+	let mut access_list = Vec::new();
+	for entry in rlp.iter() {
+		if entry.item_count().ok()? != 2 {
+			return None;
+		}
+		let address_bytes = entry.at(0).ok()?.data().ok()?;
+		if address_bytes.len() != 20 {
+			return None;
+		}
+		let mut address = EthereumAddress::default();
+		address.0.copy_from_slice(address_bytes);
+
+		let mut storage_keys = Vec::new();
+		for key_rlp in entry.at(1).ok()?.iter() {
+			let key_bytes = key_rlp.data().ok()?;
+			if key_bytes.len() != 32 {
+				return None;
+			}
+			let mut key = [0u8; 32];
+			key.copy_from_slice(key_bytes);
+			storage_keys.push(key);
+		}
+		access_list.push((address, storage_keys));
+	}
+	Some(access_list)
+}
+
+fn decode_eip1559(payload: &[u8]) -> Option<Eip1559Transaction> {
+	let rlp = Rlp::new(payload);
+	if !rlp.is_list() || rlp.item_count().ok()? != 12 {
+		return None;
+	}
+
+	let chain_id = decode_uint64(&rlp, 0)?;
+	let nonce = decode_uint64(&rlp, 1)?;
+	let max_priority_fee_per_gas = decode_uint128(&rlp, 2)?;
+	let max_fee_per_gas = decode_uint128(&rlp, 3)?;
+	let gas_limit = decode_uint64(&rlp, 4)?;
+	let to = decode_to(&rlp, 5)?;
+	let value = decode_uint128(&rlp, 6)?;
+	let data = rlp.at(7).ok()?.data().ok()?.to_vec();
+	let access_list = decode_access_list(&rlp.at(8).ok()?)?;
+	let rec_id = decode_uint64(&rlp, 9)? as u8;
+	if rec_id > 1 {
+		return None;
+	}
+	let r = decode_u256_be(&rlp, 10)?;
+	let s = decode_u256_be(&rlp, 11)?;
+	if s > SECP256K1_HALF_N {
+		return None;
+	}
+
+	let mut sig = [0u8; 65];
+	sig[..32].copy_from_slice(&r);
+	sig[32..64].copy_from_slice(&s);
+	sig[64] = rec_id;
+
+	Some(Eip1559Transaction {
+		chain_id,
+		nonce,
+		max_priority_fee_per_gas,
+		max_fee_per_gas,
+		gas_limit,
+		to,
+		value,
+		data,
+		access_list,
+		signature: EcdsaSignature(sig),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn from_hex(s: &str) -> Vec<u8> {
+		(0..s.len())
+			.step_by(2)
+			.map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+			.collect()
+	}
+
+	fn address(hex: &str) -> EthereumAddress {
+		let mut address = EthereumAddress::default();
+		address.0.copy_from_slice(&from_hex(hex));
+		address
+	}
+
+	// These three envelopes (and the malleable variant below) were built and signed offline by
+	// hand-rolling RLP encoding, keccak256, and ECDSA signing rather than using this crate's own
+	// code, so decoding and recovery here are checked against a fixture that couldn't share a bug
+	// with the implementation under test.
+	const SIGNER: &str = "e35ddecb3ae0964de6f18d145675baa371c51b2b";
+
+	#[test]
+	fn decodes_and_recovers_legacy_transaction_without_replay_protection() {
+		let raw = from_hex(
+			"f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764\
+			 0000801ca023dc8c9a4452589f34679531ff9bde2ada111d0aee11ffd99eb850f5ca6f024da04a971\
+			 c887c385aa736ea12b1a39c7cdf00e6b6321cc05fe6b1d3f6ea6ae8705d",
+		);
+		let tx = EthTransaction::decode(&raw).expect("valid legacy envelope");
+		match &tx {
+			EthTransaction::Legacy(legacy) => assert_eq!(legacy.chain_id, 0),
+			EthTransaction::Eip1559(_) => panic!("expected a legacy transaction"),
+		}
+		assert_eq!(tx.recover_signer(), Some(address(SIGNER)));
+	}
+
+	#[test]
+	fn decodes_and_recovers_legacy_eip155_transaction() {
+		let raw = from_hex(
+			"f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764\
+			 00008026a0baf15459d19a2eefb303d24514e915326d954c562e24f2b216e1a0b868c5fe0ba03963a\
+			 6c2ec704128f65311548690d166ade86d0c6720b986e6115e14ee5625c1",
+		);
+		let tx = EthTransaction::decode(&raw).expect("valid EIP-155 envelope");
+		match &tx {
+			EthTransaction::Legacy(legacy) => assert_eq!(legacy.chain_id, 1),
+			EthTransaction::Eip1559(_) => panic!("expected a legacy transaction"),
+		}
+		assert_eq!(tx.recover_signer(), Some(address(SIGNER)));
+	}
+
+	#[test]
+	fn decodes_and_recovers_eip1559_transaction() {
+		let raw = from_hex(
+			"02f8730180847735940085174876e80082520894353535353535353535353535353535353535353\
+			 588016345785d8a000080c001a0f640c4694cdfb4d0a6efd583a273980d5c482666b9fede05efddd\
+			 f0846b7bedda04c2a84bff78ada2eb93582a218cef89d93f69257623fd76bcc91c74377f396f0",
+		);
+		let tx = EthTransaction::decode(&raw).expect("valid EIP-1559 envelope");
+		assert!(matches!(tx, EthTransaction::Eip1559(_)));
+		assert_eq!(tx.recover_signer(), Some(address(SIGNER)));
+	}
+
+	#[test]
+	fn rejects_legacy_transaction_with_malleable_high_s_signature() {
+		// Same signature as `decodes_and_recovers_legacy_eip155_transaction`, with `s`
+		// flipped to `n - s` and `v`'s parity bit flipped to match (still a mathematically
+		// valid ECDSA signature for the same key, recovering to the same signer) - exactly
+		// the kind of malleable second encoding `SECP256K1_HALF_N` is meant to reject.
+		let raw = from_hex(
+			"f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764\
+			 00008025a0baf15459d19a2eefb303d24514e915326d954c562e24f2b216e1a0b868c5fe0ba0c69c59\
+			 3d138fbed709aceeab796f2e980cc66fda4827e6b4d9c10077e1e01b80",
+		);
+		assert!(EthTransaction::decode(&raw).is_none());
+	}
+}